@@ -1,9 +1,13 @@
-use std::{collections::HashMap, fs::metadata, io::ErrorKind, path::Path, process::Command, thread::sleep, time::{Duration, SystemTime}};
+use std::{collections::HashMap, fs::metadata, io::{ErrorKind, Write}, path::{Path, PathBuf}, process::{Child, Command}, sync::mpsc::channel, thread::sleep, time::{Duration, Instant, SystemTime}};
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
 
 const FILE_FORMATTED_LIST_PLACEHOLDER: &'static str = "%F";
 const FILE_SEQUENCE_PLACEHOLDER: &'static str = "%f";
 
 const SAMPLING_PERIOD_MILLIS: u64 = 50;
+const DEFAULT_RESTART_GRACE_MILLIS: u64 = 2000;
 
 fn print_help() {
     eprintln!("{} - watch for file changes
@@ -12,6 +16,52 @@ options:
     --files,   -f   begin file list
     --command, -c   begin command
     --silent,  -s   silent mode (verbose is on by default)
+    --poll[=MILLIS] use a polling loop instead of native filesystem events,
+                    sampling every MILLIS (defaults to 50ms); useful on
+                    network filesystems where events are unreliable
+    --restart, -r   don't wait for the command to exit; on each change, kill
+                     the previous run's whole process group and respawn
+    --restart-grace=MILLIS
+                    with --restart, how long to wait after SIGTERM before
+                    SIGKILL-ing a process group that won't exit (defaults to 2000ms)
+    --hash          compare a content digest instead of (just) mtime, so
+                    mtime-only touches are ignored and mtime-preserving saves
+                    are still caught; directories are digested by their
+                    sorted list of entry names and sizes
+    --ignore=GLOB   prune the recursive walk of watched directories using a
+                    .gitignore-style pattern (repeatable; later patterns win,
+                    `!` negates)
+    --gitignore     also load patterns from ./.gitignore
+    --shell, -S     pass the command as one script to a shell (instead of
+                    treating command[0] as the program and the rest as its
+                    pre-split args), so pipes/&&/quoting/redirection work;
+                    %f/%F are substituted as shell-quoted literal arguments, so
+                    a changed path is never reinterpreted as shell syntax; the
+                    changed-file lists are also exported as
+                    $WATCHWOLF_CHANGED (space-separated) and
+                    $WATCHWOLF_CHANGED_COMMA (`, `-separated)
+    --shell=PROG    like --shell, but run PROG instead of $SHELL/sh
+                    (%COMSPEC%/cmd on windows)
+    --debounce=MILLIS
+                    coalesce a burst of changes into a single run: wait for
+                    MILLIS of quiet after the first change before running the
+                    command once with the union of everything that changed
+    --debounce-mode=fixed
+                    with --debounce, don't push the deadline back out when
+                    more changes arrive during the window (default: extend)
+    --clear, -C     clear the terminal before each run, so output from the
+                    previous run doesn't pile up (uses the terminfo `clear`
+                    capability, falling back to `cls` on windows)
+    --on-success    like --clear, but only clear before the first run and after
+                    a run that succeeded, so a failing run's output stays
+                    visible until it's fixed
+    --on-failure    like --clear, but only clear before the first run and after
+                    a run that failed
+note:
+    watched directories are expanded recursively: every file and subdirectory
+    they contain is watched too, and newly created subdirectories are picked
+    up as they appear. symlinked directories are watched themselves but not
+    followed, to avoid recursing forever on a symlink cycle.
 format:
     the command string supports the following placeholders:
         %f    expands to a space-separated list of file names;
@@ -23,7 +73,7 @@ format:
 fn format_files_list(changed_files: &[&Path]) -> String {
     if changed_files.len() == 0 {
         panic!("this ain't supposed to happen");
-    } 
+    }
 
     let mut list = changed_files[0].to_str().unwrap_or("not-utf-8-path").to_owned();
 
@@ -34,10 +84,43 @@ fn format_files_list(changed_files: &[&Path]) -> String {
     list
 }
 
-fn build_cmd(changed_files: &[&Path], command: &Vec<String>) -> Command {
+fn build_cmd(changed_files: &[&Path], command: &Vec<String>, shell: Option<&str>) -> Command {
     let file_list = format_files_list(changed_files);
     let file_sequence = changed_files.iter().map(|p| p.to_str().unwrap_or("not-utf-8-path").to_string()).collect::<Vec<String>>().join(" ");
 
+    if let Some(shell_prog) = shell {
+        // %f/%F get handed to a real shell, so a changed path containing shell
+        // metacharacters (`; $() \`` etc.) must be quoted as a single literal argument,
+        // not interpolated raw -- otherwise a maliciously- or just unluckily-named file
+        // could inject arbitrary commands into the script
+        let quoted_sequence = changed_files.iter()
+            .map(|p| shell_quote(p.to_str().unwrap_or("not-utf-8-path")))
+            .collect::<Vec<String>>().join(" ");
+        let quoted_list = changed_files.iter()
+            .map(|p| shell_quote(p.to_str().unwrap_or("not-utf-8-path")))
+            .collect::<Vec<String>>().join(", ");
+
+        // substituted in one left-to-right pass, so a `%f`/`%F` inside an already
+        // substituted (quoted) path is never expanded a second time
+        let joined = command.join(" ");
+        let (mut script, mut rest) = (String::new(), joined.as_str());
+        while let Some(i) = rest.find('%') {
+            script.push_str(&rest[..i]);
+            rest = &rest[i..];
+            let (with, skip) = if rest.starts_with(FILE_FORMATTED_LIST_PLACEHOLDER) { (quoted_list.as_str(), FILE_FORMATTED_LIST_PLACEHOLDER.len()) }
+                else if rest.starts_with(FILE_SEQUENCE_PLACEHOLDER) { (quoted_sequence.as_str(), FILE_SEQUENCE_PLACEHOLDER.len()) }
+                else { ("%", 1) };
+            script.push_str(with);
+            rest = &rest[skip..];
+        }
+        script.push_str(rest);
+
+        let mut cmd = shell_invocation(shell_prog, &script);
+        cmd.env("WATCHWOLF_CHANGED", &file_sequence);
+        cmd.env("WATCHWOLF_CHANGED_COMMA", &file_list);
+        return cmd;
+    }
+
     if command.len() > 0 {
         let mut cmd = Command::new(&command[0]
                                 .replace(FILE_FORMATTED_LIST_PLACEHOLDER, &file_list)
@@ -56,13 +139,106 @@ fn build_cmd(changed_files: &[&Path], command: &Vec<String>) -> Command {
     cmd
 }
 
-fn process_changed_files<'a>(all_files: &mut HashMap<&'a Path, FileState>) -> Option<Vec<&'a Path>> {
-    let mut changes = vec![]; 
+// builds the "run a script through a shell" invocation: `sh -c`/`$SHELL -c` on unix,
+// `cmd /C` (or whatever $COMSPEC/--shell points at) on windows
+fn shell_invocation(shell_prog: &str, script: &str) -> Command {
+    let mut cmd = Command::new(shell_prog);
+    if cfg!(windows) && Path::new(shell_prog).file_stem().and_then(|s| s.to_str()).map(|s| s.eq_ignore_ascii_case("cmd")).unwrap_or(false) {
+        cmd.args(["/C", script]);
+    } else {
+        cmd.args(["-c", script]);
+    }
+    cmd
+}
+
+// quotes a single path as one literal argument for the target shell, so %f/%F
+// substitution can never be reinterpreted as shell syntax (command separators,
+// substitution, redirection, ...) by the script built in build_cmd.
+//
+// on windows, double-quoting alone is NOT enough: cmd.exe expands `%VAR%` references
+// inside a double-quoted argument too, so a path containing a literal `%` could still
+// pull in unrelated environment variable content. cmd.exe has no general escape for a
+// literal `%` in that position; doubling it (`%%`) only applies inside a batch file,
+// not to an inline `/C` script, so the only reliable defense is to refuse a path that
+// could trigger expansion rather than silently passing it through unneutralized
+fn shell_quote(path: &str) -> String {
+    if cfg!(windows) {
+        if path.contains('%') {
+            eprintln!("# warning: refusing to pass {path:?} to cmd.exe -- its `%` would still be expanded inside a quoted argument; rename the path");
+            return "\"\"".to_owned();
+        }
+        format!("\"{}\"", path.replace('"', "\"\""))
+    } else {
+        format!("'{}'", path.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(test)]
+mod shell_quote_tests {
+    use super::*;
+
+    #[test]
+    #[cfg(not(windows))]
+    fn unix_quoting_neutralizes_shell_metacharacters() {
+        let quoted = shell_quote("a; rm -rf / #");
+        assert_eq!(quoted, "'a; rm -rf / #'");
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn unix_quoting_escapes_an_embedded_single_quote() {
+        let quoted = shell_quote("it's a file");
+        assert_eq!(quoted, "'it'\\''s a file'");
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn windows_quoting_doubles_an_embedded_double_quote() {
+        let quoted = shell_quote("say \"hi\"");
+        assert_eq!(quoted, "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    #[cfg(windows)]
+    fn windows_quoting_refuses_a_path_cmd_exe_would_still_expand() {
+        // quoting alone does not stop cmd.exe from expanding %VAR% inside a quoted
+        // argument, so a path containing `%` must be refused rather than passed
+        // through looking safe while it isn't
+        let quoted = shell_quote("%PATH%.txt");
+        assert_eq!(quoted, "\"\"");
+    }
+
+    #[test]
+    fn build_cmd_quotes_each_changed_file_as_one_shell_argument() {
+        let files = [Path::new("a b"), Path::new("c;d")];
+        let cmd = build_cmd(&files, &vec!["echo".to_owned(), FILE_SEQUENCE_PLACEHOLDER.to_owned()], Some(if cfg!(windows) { "cmd" } else { "sh" }));
+        let args: Vec<String> = cmd.get_args().map(|a| a.to_string_lossy().into_owned()).collect();
+        let script = args.last().expect("shell invocation always passes a script argument");
+        assert!(script.contains(&shell_quote("a b")));
+        assert!(script.contains(&shell_quote("c;d")));
+    }
+}
+
+// resolves the shell program for --shell/-S: an explicit --shell=<prog> wins, then
+// $SHELL (unix) / %COMSPEC% (windows), then a sane platform default
+fn resolve_shell(explicit: Option<String>) -> String {
+    if let Some(prog) = explicit {
+        return prog;
+    }
+    if cfg!(windows) {
+        std::env::var("COMSPEC").unwrap_or_else(|_| "cmd".to_owned())
+    } else {
+        std::env::var("SHELL").unwrap_or_else(|_| "sh".to_owned())
+    }
+}
+
+fn process_changed_files(all_files: &mut HashMap<PathBuf, FileState>, hash: bool) -> Option<Vec<PathBuf>> {
+    let mut changes = vec![];
 
     for (f, fs) in all_files.iter_mut() {
-        let curr_fs = FileState::of(f);
+        let curr_fs = FileState::of(f, hash);
         if fs.has_changed(&curr_fs) {
-            changes.push(*f);
+            changes.push(f.clone());
             *fs = curr_fs;
         }
     }
@@ -74,16 +250,164 @@ fn process_changed_files<'a>(all_files: &mut HashMap<&'a Path, FileState>) -> Op
     }
 }
 
+// targeted counterpart to process_changed_files for the event-driven backend: checks
+// only the paths the kernel actually reported instead of re-stat'ing (and, under
+// --hash, re-hashing) the entire cache on every single event, which is what made
+// --hash pathologically expensive on large trees with editors that emit several
+// events per save
+fn process_event_paths(cache: &mut HashMap<PathBuf, FileState>, matcher: &Gitignore, event_paths: &[PathBuf], hash: bool) -> Option<Vec<PathBuf>> {
+    let mut changes = vec![];
+
+    for p in event_paths {
+        if matcher.matched(p, p.is_dir()).is_ignore() {
+            continue;
+        }
+
+        // a newly created directory isn't in the cache yet and the kernel won't
+        // separately report each file already inside it, so pull in its whole subtree
+        if p.is_dir() && !cache.contains_key(p) {
+            sync_paths(cache, &expand_paths(std::slice::from_ref(p), matcher));
+        }
+
+        let curr_fs = FileState::of(p, hash);
+        let fs = cache.entry(p.clone()).or_insert(FileState::Inexistent(SystemTime::UNIX_EPOCH));
+        if fs.has_changed(&curr_fs) {
+            changes.push(p.clone());
+            *fs = curr_fs;
+        }
+    }
+
+    if changes.is_empty() {
+        None
+    } else {
+        Some(changes)
+    }
+}
+
+// brings the cache's key set up to date with the latest directory walk, so files and
+// subdirectories created after startup get a baseline entry and are picked up on the
+// very next reconciliation; entries for paths that vanished are left in place; their
+// next `FileState::of` naturally resolves to `Inexistent`, preserving the existing
+// deletion-detection behaviour
+fn sync_paths(cache: &mut HashMap<PathBuf, FileState>, walked: &[PathBuf]) {
+    for f in walked {
+        cache.entry(f.clone()).or_insert(FileState::Inexistent(SystemTime::UNIX_EPOCH));
+    }
+}
+
+// recursively expands every watched directory into itself plus all of its contained
+// files and subdirectories, pruning entries (and, for a pruned directory, its entire
+// subtree) that match the ignore patterns
+fn expand_paths(top_level: &[PathBuf], matcher: &Gitignore) -> Vec<PathBuf> {
+    let mut expanded = vec![];
+    for p in top_level {
+        collect_paths(p, matcher, &mut expanded);
+    }
+    expanded
+}
+
+fn collect_paths(path: &Path, matcher: &Gitignore, out: &mut Vec<PathBuf>) {
+    let is_dir = path.is_dir();
+    if matcher.matched(path, is_dir).is_ignore() {
+        return;
+    }
+
+    out.push(path.to_path_buf());
+    // a symlinked directory is watched as a single entry but not walked into: following
+    // it could recurse forever on a symlink cycle (not unusual -- some build caches and
+    // `node_modules/.bin` layouts symlink back up the tree), matching the common
+    // watcher convention of not following symlinks by default
+    if is_dir && !path.is_symlink() {
+        if let Ok(entries) = std::fs::read_dir(path) {
+            for entry in entries.filter_map(|e| e.ok()) {
+                collect_paths(&entry.path(), matcher, out);
+            }
+        }
+    }
+}
+
+// compiles --ignore patterns (and optionally ./.gitignore) into an ordered matcher
+// where, per gitignore semantics, the last matching pattern wins and a leading `!`
+// re-includes a path excluded by an earlier pattern
+fn build_matcher(ignore_patterns: &[String], use_gitignore: bool) -> Gitignore {
+    let root = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    let mut builder = GitignoreBuilder::new(&root);
+
+    if use_gitignore {
+        if let Some(e) = builder.add(".gitignore") {
+            eprintln!("# failed to read ./.gitignore: {e}");
+        }
+    }
+    for pattern in ignore_patterns {
+        if let Err(e) = builder.add_line(None, pattern) {
+            eprintln!("# invalid --ignore pattern {pattern:?}: {e}");
+        }
+    }
+
+    builder.build().unwrap_or_else(|e| {
+        eprintln!("# failed to compile ignore patterns: {e}");
+        std::process::exit(5);
+    })
+}
+
+#[cfg(test)]
+mod collect_paths_tests {
+    use super::*;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("watchwolf_collect_test_{}_{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).expect("failed to create scratch dir");
+        dir
+    }
+
+    #[test]
+    fn prunes_an_ignored_directory_and_its_whole_subtree() {
+        let dir = scratch_dir("prune_subtree");
+        std::fs::create_dir_all(dir.join("node_modules/nested")).unwrap();
+        std::fs::write(dir.join("node_modules/nested/f.txt"), b"x").unwrap();
+        std::fs::write(dir.join("keep.txt"), b"x").unwrap();
+
+        let matcher = build_matcher(&["node_modules".to_owned()], false);
+        let mut out = vec![];
+        collect_paths(&dir, &matcher, &mut out);
+
+        assert!(out.contains(&dir.join("keep.txt")));
+        assert!(!out.iter().any(|p| p.starts_with(dir.join("node_modules"))), "an ignored directory's contents should never be walked into");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn does_not_follow_a_symlinked_directory_even_when_it_cycles_back_up_the_tree() {
+        let dir = scratch_dir("symlink_cycle");
+        std::fs::create_dir_all(dir.join("real")).unwrap();
+        std::fs::write(dir.join("real/f.txt"), b"x").unwrap();
+        // a symlink inside "real" pointing back at "dir" itself -- following it would
+        // recurse forever without the is_symlink() guard
+        std::os::unix::fs::symlink(&dir, dir.join("real/loop")).unwrap();
+
+        let matcher = build_matcher(&[], false);
+        let mut out = vec![];
+        collect_paths(&dir, &matcher, &mut out);
+
+        // the symlink itself is listed as one entry, but never walked into
+        let loop_link = dir.join("real/loop");
+        assert!(out.contains(&loop_link));
+        assert!(out.contains(&dir.join("real/f.txt")));
+        assert!(!out.iter().any(|p| p != &loop_link && p.starts_with(&loop_link)), "a symlinked directory must not be recursed into");
+    }
+}
+
 enum FileState {
-    IsFile(SystemTime),
-    IsDir(SystemTime),
+    IsFile(SystemTime, Option<blake3::Hash>),
+    IsDir(SystemTime, Option<blake3::Hash>),
     IsOther(SystemTime),
     Inexistent(SystemTime),
     NoPerm(SystemTime),
 }
 
 impl FileState {
-    fn of(path: &Path) -> FileState {
+    fn of(path: &Path, hash: bool) -> FileState {
         let md = match metadata(path) {
             Err(e) => match e.kind() {
                 ErrorKind::NotFound => return FileState::Inexistent(SystemTime::UNIX_EPOCH),
@@ -95,21 +419,62 @@ impl FileState {
 
         let tm = md.modified().expect("mod time unavailable on this platform");
         if md.is_file() {
-            return FileState::IsFile(tm);
+            let digest = if hash { Self::hash_file(path) } else { None };
+            return FileState::IsFile(tm, digest);
         } else if md.is_dir() {
-            return FileState::IsDir(tm);
+            let digest = if hash { Self::hash_dir(path) } else { None };
+            return FileState::IsDir(tm, digest);
         }
         return FileState::IsOther(tm);
     }
 
+    // streaming digest of the file's contents, so editors that touch mtime without
+    // changing bytes (or atomic save-and-rename that resets mtime) don't cause a false
+    // trigger, and tools that preserve mtime on a real change don't get missed
+    fn hash_file(path: &Path) -> Option<blake3::Hash> {
+        let mut file = std::fs::File::open(path).ok()?;
+        let mut hasher = blake3::Hasher::new();
+        std::io::copy(&mut file, &mut hasher).ok()?;
+        Some(hasher.finalize())
+    }
+
+    // a directory's own mtime is unreliable across platforms for detecting additions
+    // or removals inside it, so digest the sorted (name, size) pairs of its entries
+    // instead
+    fn hash_dir(path: &Path) -> Option<blake3::Hash> {
+        let mut entries: Vec<(String, u64)> = std::fs::read_dir(path).ok()?
+            .filter_map(|e| e.ok())
+            .filter_map(|e| {
+                let name = e.file_name().to_str()?.to_owned();
+                let size = e.metadata().ok()?.len();
+                Some((name, size))
+            })
+            .collect();
+        entries.sort();
+
+        let mut hasher = blake3::Hasher::new();
+        for (name, size) in entries {
+            hasher.update(name.as_bytes());
+            hasher.update(&size.to_le_bytes());
+        }
+        Some(hasher.finalize())
+    }
+
     fn has_changed(&self, new_state: &FileState) -> bool {
-        !self.has_similar_state(new_state) || self.system_time() < new_state.system_time()
+        if !self.has_similar_state(new_state) {
+            return true;
+        }
+
+        match (self.digest(), new_state.digest()) {
+            (Some(a), Some(b)) => a != b,
+            _ => self.system_time() < new_state.system_time(),
+        }
     }
 
     fn has_similar_state(&self, other: &FileState) -> bool {
         match (self, other) {
-            (FileState::IsFile(_), FileState::IsFile(_)) => true,
-            (FileState::IsDir(_), FileState::IsDir(_)) => true,
+            (FileState::IsFile(..), FileState::IsFile(..)) => true,
+            (FileState::IsDir(..), FileState::IsDir(..)) => true,
             (FileState::IsOther(_), FileState::IsOther(_)) => true,
             (FileState::Inexistent(_), FileState::Inexistent(_)) => true,
             (FileState::NoPerm(_), FileState::NoPerm(_)) => true,
@@ -117,41 +482,631 @@ impl FileState {
         }
     }
 
+    fn digest(&self) -> Option<blake3::Hash> {
+        match self {
+            Self::IsFile(_, d) | Self::IsDir(_, d) => *d,
+            _ => None,
+        }
+    }
+
     fn system_time(&self) -> SystemTime {
         match self {
-            Self::IsFile(t) | Self::IsDir(t) | Self::IsOther(t) | Self::Inexistent(t) | Self::NoPerm(t) => *t,
+            Self::IsFile(t, _) | Self::IsDir(t, _) | Self::IsOther(t) | Self::Inexistent(t) | Self::NoPerm(t) => *t,
         }
     }
 }
 
-fn watch(files: Vec<&Path>, command: Vec<String>, silent: bool) {
-    let shellcmd = command.join(" ");
+#[cfg(test)]
+mod file_state_hash_tests {
+    use super::*;
 
-    let mut file_state_cache = HashMap::new();
-    for f in files {
-        file_state_cache.insert(f, FileState::of(f));
+    // gives each test its own scratch directory so concurrent test threads don't
+    // trample each other's fixtures
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("watchwolf_hash_test_{}_{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).expect("failed to create scratch dir");
+        dir
     }
 
-    loop {
-        sleep(Duration::from_millis(SAMPLING_PERIOD_MILLIS));
-        match process_changed_files(&mut file_state_cache) {
-            None => continue,
-            Some(changes) => {
-                let mut cmd = build_cmd(&changes, &command);
-                if !silent {
-                    eprintln!("# found changes in: {} -- shell: {}", format_files_list(&changes), shellcmd);
+    #[test]
+    fn hash_file_is_stable_for_unchanged_contents() {
+        let dir = scratch_dir("hash_file_stable");
+        let f = dir.join("a.txt");
+        std::fs::write(&f, b"hello").unwrap();
+
+        assert_eq!(FileState::hash_file(&f), FileState::hash_file(&f));
+    }
+
+    #[test]
+    fn hash_file_differs_when_contents_differ() {
+        let dir = scratch_dir("hash_file_differs");
+        let f = dir.join("a.txt");
+
+        std::fs::write(&f, b"hello").unwrap();
+        let before = FileState::hash_file(&f);
+        std::fs::write(&f, b"goodbye").unwrap();
+        let after = FileState::hash_file(&f);
+
+        assert_ne!(before, after);
+    }
+
+    #[test]
+    fn hash_file_returns_none_for_a_missing_file() {
+        let dir = scratch_dir("hash_file_missing");
+        assert_eq!(FileState::hash_file(&dir.join("does-not-exist")), None);
+    }
+
+    #[test]
+    fn hash_dir_is_unaffected_by_entry_mtime_but_reacts_to_additions() {
+        let dir = scratch_dir("hash_dir_additions");
+        std::fs::write(dir.join("a.txt"), b"same size").unwrap();
+
+        let before = FileState::hash_dir(&dir);
+        // touching an existing entry's mtime without changing its (name, size) pair
+        // must not move the digest -- hash_dir only looks at name/size, not mtime
+        std::fs::write(dir.join("a.txt"), b"same size").unwrap();
+        assert_eq!(before, FileState::hash_dir(&dir));
+
+        std::fs::write(dir.join("b.txt"), b"new file").unwrap();
+        assert_ne!(before, FileState::hash_dir(&dir), "adding an entry should change the digest");
+    }
+}
+
+// how run_for_changes reacts to a detected change: either block until the command exits
+// (the original behaviour), or manage a long-running child across iterations,
+// killing its whole process group before respawning
+enum RunMode {
+    Blocking,
+    Restart { grace: Duration },
+}
+
+// whether noting a fresh change while a debounce window is already open pushes the
+// deadline back out (the default) or leaves the original deadline alone
+#[derive(Clone, Copy)]
+enum DebounceMode {
+    Extend,
+    Fixed,
+}
+
+// coalesces a burst of changes (e.g. `git checkout`, `cargo fmt` touching many files
+// within milliseconds of each other) into a single run with the union of paths,
+// instead of firing the command once per detected change
+struct Debouncer {
+    millis: u64,
+    mode: DebounceMode,
+    pending: std::collections::HashSet<PathBuf>,
+    deadline: Option<Instant>,
+}
+
+impl Debouncer {
+    fn new(millis: u64, mode: DebounceMode) -> Debouncer {
+        Debouncer { millis, mode, pending: std::collections::HashSet::new(), deadline: None }
+    }
+
+    fn note_changes(&mut self, changes: &[&Path]) {
+        self.pending.extend(changes.iter().map(|p| p.to_path_buf()));
+        match self.mode {
+            DebounceMode::Extend => self.deadline = Some(Instant::now() + Duration::from_millis(self.millis)),
+            DebounceMode::Fixed => { self.deadline.get_or_insert_with(|| Instant::now() + Duration::from_millis(self.millis)); },
+        }
+    }
+
+    // how long until the open debounce window closes; `None` means no window is open,
+    // so a caller blocking on an event source can wait indefinitely
+    fn time_until_deadline(&self) -> Option<Duration> {
+        self.deadline.map(|d| d.saturating_duration_since(Instant::now()))
+    }
+
+    fn ready(&self) -> bool {
+        !self.pending.is_empty() && self.deadline.is_some_and(|d| Instant::now() >= d)
+    }
+
+    fn take(&mut self) -> Vec<PathBuf> {
+        self.deadline = None;
+        self.pending.drain().collect()
+    }
+}
+
+#[cfg(test)]
+mod debouncer_tests {
+    use super::*;
+
+    #[test]
+    fn not_ready_with_no_changes_noted() {
+        let d = Debouncer::new(30, DebounceMode::Extend);
+        assert!(!d.ready());
+    }
+
+    #[test]
+    fn extend_mode_pushes_the_deadline_back_on_each_change() {
+        let mut d = Debouncer::new(60, DebounceMode::Extend);
+        let a = PathBuf::from("a");
+        let b = PathBuf::from("b");
+
+        d.note_changes(&[&a]);
+        sleep(Duration::from_millis(40));
+        // noted before the first window closed, so the deadline should have moved out
+        // another 60ms from here, not still be counting down from the first note
+        d.note_changes(&[&b]);
+        sleep(Duration::from_millis(40));
+        assert!(!d.ready(), "extend mode should not fire before 60ms of quiet follows the last change");
+
+        sleep(Duration::from_millis(30));
+        assert!(d.ready(), "extend mode should fire once 60ms of quiet has passed since the last change");
+
+        let pending = d.take();
+        assert_eq!(pending.len(), 2);
+        assert!(pending.contains(&a));
+        assert!(pending.contains(&b));
+        assert!(!d.ready(), "take() should close the window and clear the pending set");
+    }
+
+    #[test]
+    fn fixed_mode_keeps_the_original_deadline() {
+        let mut d = Debouncer::new(60, DebounceMode::Fixed);
+        let a = PathBuf::from("a");
+        let b = PathBuf::from("b");
+
+        d.note_changes(&[&a]);
+        sleep(Duration::from_millis(40));
+        // noted 40ms into a 60ms window; fixed mode must not push the deadline out, so
+        // the window should still close ~20ms from here, not 60ms from here
+        d.note_changes(&[&b]);
+        sleep(Duration::from_millis(30));
+        assert!(d.ready(), "fixed mode should fire on the original deadline, unaffected by the second change");
+
+        let pending = d.take();
+        assert_eq!(pending.len(), 2);
+    }
+
+    #[test]
+    fn ready_is_false_before_the_deadline_and_true_at_or_after_it() {
+        let mut d = Debouncer::new(40, DebounceMode::Extend);
+        let a = PathBuf::from("a");
+        d.note_changes(&[&a]);
+
+        assert!(!d.ready());
+        sleep(Duration::from_millis(60));
+        assert!(d.ready());
+    }
+}
+
+// the pgid/pid of whatever --restart child is currently alive, so the interrupt
+// handler installed by install_interrupt_handler (below) knows what to tear down;
+// 0 means "nothing running". Async-signal-safe on unix (just an atomic store/load),
+// and on windows the console control handler runs on its own OS-spawned thread so
+// ordinary synchronization is fine there too
+#[cfg(unix)]
+static RESTART_CHILD_PGID: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(0);
+#[cfg(windows)]
+static RESTART_CHILD_PID: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+
+// a spawned child placed in its own process group, so that shell-wrapped commands
+// and the children they fork can all be torn down together instead of being orphaned
+struct RunningProcess {
+    child: Child,
+}
+
+// the outcome of tearing down a --restart child: either it had already exited on its
+// own before we got around to it (a real verdict on the last run), or we're the ones
+// who killed it to make room for the respawn (not a verdict on anything -- it was
+// interrupted, not judged)
+enum Termination {
+    ExitedOnOwn(std::process::ExitStatus),
+    KilledByUs,
+}
+
+impl RunningProcess {
+    fn spawn(cmd: &mut Command) -> std::io::Result<RunningProcess> {
+        configure_process_group(cmd);
+        let child = cmd.spawn()?;
+        record_restart_child(&child);
+        Ok(RunningProcess { child })
+    }
+
+    // SIGTERMs the whole group, waits up to `grace` for it to exit, then SIGKILLs it
+    #[cfg(unix)]
+    fn terminate(mut self, grace: Duration) -> Termination {
+        let pgid = self.child.id() as libc::pid_t;
+        clear_restart_child();
+
+        if let Ok(Some(status)) = self.child.try_wait() {
+            return Termination::ExitedOnOwn(status);
+        }
+        unsafe { libc::killpg(pgid, libc::SIGTERM); }
+
+        let deadline = Instant::now() + grace;
+        loop {
+            match self.child.try_wait() {
+                Ok(None) if Instant::now() >= deadline => {
+                    unsafe { libc::killpg(pgid, libc::SIGKILL); }
+                    let _ = self.child.wait();
+                    return Termination::KilledByUs;
+                },
+                Ok(None) => sleep(Duration::from_millis(20)),
+                _ => return Termination::KilledByUs,
+            }
+        }
+    }
+
+    // windows has no SIGTERM equivalent for a process group; ask the group created by
+    // CREATE_NEW_PROCESS_GROUP to exit via taskkill's tree-kill, then fall back to a
+    // hard kill of just the parent if it's still around after the grace period
+    #[cfg(windows)]
+    fn terminate(mut self, grace: Duration) -> Termination {
+        let pid = self.child.id();
+        clear_restart_child();
+
+        if let Ok(Some(status)) = self.child.try_wait() {
+            return Termination::ExitedOnOwn(status);
+        }
+        let _ = Command::new("taskkill").args(["/PID", &pid.to_string(), "/T"]).status();
+
+        let deadline = Instant::now() + grace;
+        while Instant::now() < deadline {
+            if let Ok(Some(_)) = self.child.try_wait() {
+                return Termination::KilledByUs;
+            }
+            sleep(Duration::from_millis(20));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        Termination::KilledByUs
+    }
+}
+
+#[cfg(unix)]
+fn configure_process_group(cmd: &mut Command) {
+    use std::os::unix::process::CommandExt;
+    cmd.process_group(0);
+}
+
+#[cfg(windows)]
+fn configure_process_group(cmd: &mut Command) {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NEW_PROCESS_GROUP: u32 = 0x00000200;
+    cmd.creation_flags(CREATE_NEW_PROCESS_GROUP);
+}
+
+#[cfg(unix)]
+fn record_restart_child(child: &Child) {
+    RESTART_CHILD_PGID.store(child.id() as libc::pid_t, std::sync::atomic::Ordering::SeqCst);
+}
+#[cfg(windows)]
+fn record_restart_child(child: &Child) {
+    RESTART_CHILD_PID.store(child.id(), std::sync::atomic::Ordering::SeqCst);
+}
+
+#[cfg(unix)]
+fn clear_restart_child() {
+    RESTART_CHILD_PGID.store(0, std::sync::atomic::Ordering::SeqCst);
+}
+#[cfg(windows)]
+fn clear_restart_child() {
+    RESTART_CHILD_PID.store(0, std::sync::atomic::Ordering::SeqCst);
+}
+
+// SIGINT is delivered to watchwolf's own foreground process group, not to a --restart
+// child's detached group (it's detached precisely so the whole group can be killed on
+// every respawn without signalling watchwolf itself) -- so without this, Ctrl-C kills watchwolf and orphans whatever
+// dev server it had running. Forward the interrupt to the child's group before exiting.
+#[cfg(unix)]
+extern "C" fn forward_interrupt_to_restart_child(_signum: i32) {
+    let pgid = RESTART_CHILD_PGID.load(std::sync::atomic::Ordering::SeqCst);
+    if pgid != 0 {
+        unsafe { libc::killpg(pgid, libc::SIGTERM); }
+    }
+    unsafe { libc::_exit(130) }; // 128 + SIGINT, matching the shell's usual convention
+}
+
+#[cfg(unix)]
+fn install_interrupt_handler() {
+    unsafe { libc::signal(libc::SIGINT, forward_interrupt_to_restart_child as *const () as libc::sighandler_t); }
+}
+
+// on windows the console control handler runs on a dedicated OS thread, so (unlike a
+// unix signal handler) it's fine to do ordinary blocking work like spawning taskkill
+#[cfg(windows)]
+extern "system" fn forward_interrupt_to_restart_child(ctrl_type: u32) -> i32 {
+    const CTRL_C_EVENT: u32 = 0;
+    const CTRL_BREAK_EVENT: u32 = 1;
+    if ctrl_type == CTRL_C_EVENT || ctrl_type == CTRL_BREAK_EVENT {
+        let pid = RESTART_CHILD_PID.load(std::sync::atomic::Ordering::SeqCst);
+        if pid != 0 {
+            let _ = Command::new("taskkill").args(["/PID", &pid.to_string(), "/T"]).status();
+        }
+        std::process::exit(130);
+    }
+    0 // not handled; let the default handler (and any other registered handlers) run
+}
+
+#[cfg(windows)]
+#[link(name = "kernel32")]
+extern "system" {
+    fn SetConsoleCtrlHandler(handler: extern "system" fn(u32) -> i32, add: i32) -> i32;
+}
+
+#[cfg(windows)]
+fn install_interrupt_handler() {
+    unsafe { SetConsoleCtrlHandler(forward_interrupt_to_restart_child, 1); }
+}
+
+// the toggles that shape how a change is detected and reacted to, threaded through
+// the whole watch/reconcile/run call chain as one value instead of a growing list of
+// positional bool/Option arguments
+struct WatchOptions {
+    silent: bool,
+    mode: RunMode,
+    hash: bool,
+    shell: Option<String>,
+    debounce: Option<(u64, DebounceMode)>,
+    clear: Option<ClearPolicy>,
+}
+
+// state that evolves over the life of a watch session: the change-detection cache,
+// whatever process is alive under --restart, the open debounce window (if any), and
+// the previous run's outcome (for --clear --on-success/--on-failure)
+struct WatchState {
+    file_state_cache: HashMap<PathBuf, FileState>,
+    running: Option<RunningProcess>,
+    debouncer: Option<Debouncer>,
+    last_success: Option<bool>,
+}
+
+impl WatchState {
+    fn new(file_state_cache: HashMap<PathBuf, FileState>, opts: &WatchOptions) -> WatchState {
+        WatchState {
+            file_state_cache,
+            running: None,
+            debouncer: opts.debounce.map(|(millis, mode)| Debouncer::new(millis, mode)),
+            last_success: None,
+        }
+    }
+}
+
+// runs the reconciliation step against the cache and, if anything changed, builds and
+// runs the command, printing the same banners regardless of which backend woke us up
+fn reconcile_and_run(state: &mut WatchState, command: &Vec<String>, shellcmd: &str, opts: &WatchOptions) {
+    let changes = process_changed_files(&mut state.file_state_cache, opts.hash);
+    handle_changes(changes, state, command, shellcmd, opts);
+}
+
+// shared tail of reconciliation: feed freshly detected changes to the debouncer (or
+// run immediately with no --debounce), then fire a run if an open window just closed.
+// shared so both the full-rescan and targeted-event reconciliation paths funnel
+// through the same debounce/run machinery and print the same banners
+fn handle_changes(changes: Option<Vec<PathBuf>>, state: &mut WatchState, command: &Vec<String>, shellcmd: &str, opts: &WatchOptions) {
+    if let Some(changes) = changes {
+        let refs: Vec<&Path> = changes.iter().map(PathBuf::as_path).collect();
+        if let Some(debouncer) = state.debouncer.as_mut() {
+            // --debounce: stash the union of changed paths and let the deadline below decide
+            debouncer.note_changes(&refs);
+        } else {
+            // no --debounce: run immediately, exactly as before
+            run_for_changes(&refs, state, command, shellcmd, opts);
+        }
+    }
+
+    let ready = state.debouncer.as_ref().is_some_and(Debouncer::ready);
+    if ready {
+        let pending = state.debouncer.as_mut().unwrap().take();
+        let pending_refs: Vec<&Path> = pending.iter().map(|p| p.as_path()).collect();
+        run_for_changes(&pending_refs, state, command, shellcmd, opts);
+    }
+}
+
+// whether --clear should reset the terminal before every run, or only before a run
+// that follows a success/failure of the previous one
+#[derive(Clone, Copy)]
+enum ClearPolicy {
+    Always,
+    OnSuccess,
+    OnFailure,
+}
+
+fn should_clear(clear: Option<ClearPolicy>, last_success: Option<bool>) -> bool {
+    match clear {
+        None => false,
+        Some(ClearPolicy::Always) => true,
+        Some(ClearPolicy::OnSuccess) => last_success.unwrap_or(true),
+        Some(ClearPolicy::OnFailure) => last_success.is_none_or(|s| !s),
+    }
+}
+
+#[cfg(test)]
+mod should_clear_tests {
+    use super::*;
+
+    #[test]
+    fn no_policy_never_clears() {
+        assert!(!should_clear(None, None));
+        assert!(!should_clear(None, Some(true)));
+        assert!(!should_clear(None, Some(false)));
+    }
+
+    #[test]
+    fn always_clears_regardless_of_the_previous_run() {
+        assert!(should_clear(Some(ClearPolicy::Always), None));
+        assert!(should_clear(Some(ClearPolicy::Always), Some(true)));
+        assert!(should_clear(Some(ClearPolicy::Always), Some(false)));
+    }
+
+    #[test]
+    fn on_success_clears_before_the_first_run_and_after_a_success_only() {
+        assert!(should_clear(Some(ClearPolicy::OnSuccess), None), "no previous run yet -- treat as clear so the first run starts on a clean screen");
+        assert!(should_clear(Some(ClearPolicy::OnSuccess), Some(true)));
+        assert!(!should_clear(Some(ClearPolicy::OnSuccess), Some(false)));
+    }
+
+    #[test]
+    fn on_failure_clears_before_the_first_run_and_after_a_failure_only() {
+        assert!(should_clear(Some(ClearPolicy::OnFailure), None), "no previous run yet -- treat as clear so the first run starts on a clean screen");
+        assert!(should_clear(Some(ClearPolicy::OnFailure), Some(false)));
+        assert!(!should_clear(Some(ClearPolicy::OnFailure), Some(true)));
+    }
+}
+
+// resets the terminal via the terminfo `clear` capability, falling back to `cls` on
+// windows and to a raw ANSI reset if neither is on PATH
+fn clear_terminal() {
+    let cleared = if cfg!(windows) {
+        Command::new("cmd").args(["/C", "cls"]).status()
+    } else {
+        Command::new("clear").status()
+    };
+
+    if !cleared.map(|s| s.success()).unwrap_or(false) {
+        print!("\x1B[2J\x1B[H");
+        let _ = std::io::stdout().flush();
+    }
+}
+
+// builds and runs (or restarts) the command for an already-determined set of changes;
+// shared by the immediate path and the debounced path so both print the same banners
+fn run_for_changes(changes: &[&Path], state: &mut WatchState, command: &Vec<String>, shellcmd: &str, opts: &WatchOptions) {
+    if changes.is_empty() {
+        return;
+    }
+
+    if should_clear(opts.clear, state.last_success) {
+        clear_terminal();
+    }
+
+    let mut cmd = build_cmd(changes, command, opts.shell.as_deref());
+    if !opts.silent {
+        eprintln!("# found changes in: {} -- shell: {}", format_files_list(changes), shellcmd);
+    }
+
+    match &opts.mode {
+        RunMode::Blocking => {
+            match cmd.status() {
+                Err(e) => {
+                    eprintln!("# failed to execute command: {e}");
+                    state.last_success = Some(false);
+                },
+                Ok(s) => {
+                    eprintln!("# exit status: {}", s.code().map(|x| x.to_string()).unwrap_or("terminated".to_owned()));
+                    state.last_success = Some(s.success());
+                },
+            }
+        },
+        RunMode::Restart { grace } => {
+            if let Some(prev) = state.running.take() {
+                if !opts.silent {
+                    eprintln!("# restarting: terminating previous process group");
                 }
-                match cmd.status() {
-                    Err(e) => eprintln!("# failed to execute command: {e}"),
-                    Ok(s) => {
-                        eprintln!("# exit status: {}", s.code().map(|x| x.to_string()).unwrap_or("terminated".to_owned()));
-                    },
+                // only a run that had already exited on its own says anything about
+                // success/failure; a restart we forced ourselves is an interruption, not
+                // a verdict, so leave last_success untouched rather than recording our
+                // own SIGTERM/taskkill as "the command failed"
+                if let Termination::ExitedOnOwn(status) = prev.terminate(*grace) {
+                    state.last_success = Some(status.success());
                 }
             }
+            match RunningProcess::spawn(&mut cmd) {
+                Err(e) => eprintln!("# failed to execute command: {e}"),
+                Ok(child) => {
+                    eprintln!("# spawned pid {}", child.child.id());
+                    state.running = Some(child);
+                },
+            }
+        },
+    }
+}
+
+// polling fallback: re-stats every watched path on a fixed timer. kept around for
+// network filesystems (nfs/smb/etc.) whose kernels don't reliably deliver inotify/
+// kqueue/ReadDirectoryChanges events
+fn watch_poll(top_level: Vec<PathBuf>, matcher: Gitignore, command: Vec<String>, opts: WatchOptions, poll_millis: u64) {
+    let shellcmd = command.join(" ");
+
+    let mut file_state_cache = HashMap::new();
+    for f in expand_paths(&top_level, &matcher) {
+        let state = FileState::of(&f, opts.hash);
+        file_state_cache.insert(f, state);
+    }
+
+    let mut state = WatchState::new(file_state_cache, &opts);
+    loop {
+        sleep(Duration::from_millis(poll_millis));
+        sync_paths(&mut state.file_state_cache, &expand_paths(&top_level, &matcher));
+        reconcile_and_run(&mut state, &command, &shellcmd, &opts);
+    }
+}
+
+// tries to arm a native watch on `path`; a watch target is allowed to not exist yet
+// (e.g. `-f not-yet-created.txt`), and an OS can also refuse a watch (inotify watch
+// limit on a huge recursive tree), so a failure here must not bring the whole process
+// down -- warn and fall back to watching the nearest existing ancestor directory
+// instead, so the target is still picked up once its parent notices it appear
+fn watch_or_warn(watcher: &mut RecommendedWatcher, path: &Path) {
+    let recursive_mode = if path.is_dir() { RecursiveMode::Recursive } else { RecursiveMode::NonRecursive };
+    if let Err(e) = watcher.watch(path, recursive_mode) {
+        eprintln!("# warning: failed to watch {}: {e}", path.display());
+        match path.parent() {
+            Some(parent) if parent != path => {
+                // a bare relative name like `foo.txt` has the empty path as its parent
+                let parent = if parent.as_os_str().is_empty() { Path::new(".") } else { parent };
+                eprintln!("# falling back to watching parent directory {}", parent.display());
+                watch_or_warn(watcher, parent);
+            },
+            _ => eprintln!("# giving up on watching {}; changes to it will not be detected", path.display()),
         }
     }
 }
 
+// event-driven backend: blocks on the kernel's native notification mechanism
+// (inotify/kqueue/ReadDirectoryChanges, picked by `notify::recommended_watcher`) and
+// only re-runs the reconciliation step when the kernel says something happened
+fn watch_events(top_level: Vec<PathBuf>, matcher: Gitignore, command: Vec<String>, opts: WatchOptions) {
+    let shellcmd = command.join(" ");
+
+    let mut file_state_cache = HashMap::new();
+    for f in expand_paths(&top_level, &matcher) {
+        let state = FileState::of(&f, opts.hash);
+        file_state_cache.insert(f, state);
+    }
+
+    let (tx, rx) = channel();
+    let mut watcher: RecommendedWatcher = notify::recommended_watcher(tx)
+        .expect("failed to set up native filesystem watcher");
+    for f in &top_level {
+        // directories are watched recursively so the kernel reports events for
+        // everything already inside them; a subdirectory created after we started
+        // watching is pulled into the cache by process_event_paths when it's reported
+        watch_or_warn(&mut watcher, f);
+    }
+
+    let mut state = WatchState::new(file_state_cache, &opts);
+    loop {
+        // with an open debounce window we still have to wake up once it closes even
+        // if no further event arrives in the meantime; with no window open, block
+        // indefinitely for the next event like the plain (non-debounced) loop did
+        let timeout = state.debouncer.as_ref()
+            .and_then(Debouncer::time_until_deadline)
+            .unwrap_or(Duration::from_secs(60 * 60));
+
+        match rx.recv_timeout(timeout) {
+            Ok(Err(e)) => eprintln!("# watch error: {e}"),
+            Ok(Ok(event)) => {
+                let changes = process_event_paths(&mut state.file_state_cache, &matcher, &event.paths, opts.hash);
+                handle_changes(changes, &mut state, &command, &shellcmd, &opts);
+            },
+            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => break,
+            // no fresh event, but an open debounce window may have just closed
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => handle_changes(None, &mut state, &command, &shellcmd, &opts),
+        }
+    }
+}
+
+fn watch(top_level: Vec<PathBuf>, matcher: Gitignore, command: Vec<String>, opts: WatchOptions, poll_millis: Option<u64>) {
+    match poll_millis {
+        Some(millis) => watch_poll(top_level, matcher, command, opts, millis),
+        None => watch_events(top_level, matcher, command, opts),
+    }
+}
+
 fn main() {
     let mut accepting_files = false;
     let mut accepting_command = false;
@@ -160,6 +1115,18 @@ fn main() {
     let mut cmd_args = vec![];
 
     let mut silent = false;
+    let mut poll_millis: Option<u64> = None;
+    let mut restart = false;
+    let mut restart_grace_millis = DEFAULT_RESTART_GRACE_MILLIS;
+    let mut hash = false;
+    let mut ignore_patterns: Vec<String> = vec![];
+    let mut use_gitignore = false;
+    let mut shell_mode = false;
+    let mut shell_override: Option<String> = None;
+    let mut debounce_millis: Option<u64> = None;
+    let mut debounce_mode = DebounceMode::Extend;
+    let mut clear_flag = false;
+    let mut clear_scope: Option<ClearPolicy> = None;
 
     for arg in std::env::args().skip(1) {
         if arg.starts_with('-') {
@@ -179,6 +1146,69 @@ fn main() {
                 "--silent" | "-s" => {
                     silent = true;
                 },
+                "--poll" => {
+                    poll_millis = Some(SAMPLING_PERIOD_MILLIS);
+                },
+                _ if arg.starts_with("--poll=") => {
+                    let millis = arg["--poll=".len()..].parse::<u64>()
+                        .unwrap_or_else(|_| {
+                            eprintln!("invalid value for --poll: {arg} -- try --help");
+                            std::process::exit(3);
+                        });
+                    poll_millis = Some(millis);
+                },
+                "--restart" | "-r" => {
+                    restart = true;
+                },
+                _ if arg.starts_with("--restart-grace=") => {
+                    restart_grace_millis = arg["--restart-grace=".len()..].parse::<u64>()
+                        .unwrap_or_else(|_| {
+                            eprintln!("invalid value for --restart-grace: {arg} -- try --help");
+                            std::process::exit(3);
+                        });
+                },
+                "--hash" => {
+                    hash = true;
+                },
+                _ if arg.starts_with("--ignore=") => {
+                    ignore_patterns.push(arg["--ignore=".len()..].to_owned());
+                },
+                "--gitignore" => {
+                    use_gitignore = true;
+                },
+                "--shell" | "-S" => {
+                    shell_mode = true;
+                },
+                _ if arg.starts_with("--shell=") => {
+                    shell_mode = true;
+                    shell_override = Some(arg["--shell=".len()..].to_owned());
+                },
+                _ if arg.starts_with("--debounce=") => {
+                    debounce_millis = Some(arg["--debounce=".len()..].parse::<u64>()
+                        .unwrap_or_else(|_| {
+                            eprintln!("invalid value for --debounce: {arg} -- try --help");
+                            std::process::exit(3);
+                        }));
+                },
+                _ if arg.starts_with("--debounce-mode=") => {
+                    debounce_mode = match &arg["--debounce-mode=".len()..] {
+                        "extend" => DebounceMode::Extend,
+                        "fixed" => DebounceMode::Fixed,
+                        _ => {
+                            eprintln!("invalid value for --debounce-mode: {arg} -- try --help");
+                            std::process::exit(3);
+                        },
+                    };
+                },
+                "--clear" | "-C" => {
+                    clear_flag = true;
+                },
+                "--on-success" => {
+                    clear_scope = Some(ClearPolicy::OnSuccess);
+                },
+                "--on-failure" => {
+                    clear_scope = Some(ClearPolicy::OnFailure);
+                },
                 _ => {
                     eprintln!("invalid option: {arg} -- try --help");
                     std::process::exit(1);
@@ -201,6 +1231,26 @@ fn main() {
         std::process::exit(4);
     }
 
-    let files = files.iter().map(Path::new).collect();
-    watch(files, cmd_args, silent);
+    let mode = if restart {
+        // --restart children live in their own detached process group (see
+        // configure_process_group) so each respawn can kill the whole group; that also
+        // means Ctrl-C no longer reaches them for free, so forward it by hand
+        install_interrupt_handler();
+        RunMode::Restart { grace: Duration::from_millis(restart_grace_millis) }
+    } else {
+        RunMode::Blocking
+    };
+
+    let shell = if shell_mode { Some(resolve_shell(shell_override)) } else { None };
+    let debounce = debounce_millis.map(|millis| (millis, debounce_mode));
+    let clear = if clear_flag || clear_scope.is_some() {
+        Some(clear_scope.unwrap_or(ClearPolicy::Always))
+    } else {
+        None
+    };
+
+    let matcher = build_matcher(&ignore_patterns, use_gitignore);
+    let files = files.into_iter().map(PathBuf::from).collect();
+    let opts = WatchOptions { silent, mode, hash, shell, debounce, clear };
+    watch(files, matcher, cmd_args, opts, poll_millis);
 }